@@ -0,0 +1,255 @@
+// Copyright (C) 2019-2022 Aleo Systems Inc.
+// This file is part of the snarkVM library.
+
+// The snarkVM library is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// The snarkVM library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with the snarkVM library. If not, see <https://www.gnu.org/licenses/>.
+
+use snarkvm_console::{
+    prelude::Network,
+    program::{ElementType, EntryType, Identifier, Program, ValueType},
+};
+
+use serde::Serialize;
+
+/// A machine-readable description of a plaintext, record, or aggregate type, turning a
+/// deployed program's declared interface into typed, client-consumable descriptors.
+#[derive(Clone, Debug, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum TypeAbi {
+    /// A primitive literal, e.g. `field`, `u64`, or `boolean`.
+    Literal { literal: String },
+    /// A named struct, with its member names and types resolved from the program.
+    Struct { name: String, members: Vec<MemberAbi> },
+    /// A vector, recursively describing its element type.
+    Vector { element: Box<TypeAbi> },
+    /// A record, with its entries resolved from the program's record definitions.
+    Record { name: String, entries: Vec<RecordEntryAbi> },
+    /// A record defined in another, external program.
+    ExternalRecord { program_id: String, name: String },
+}
+
+/// A single named member of a struct.
+#[derive(Clone, Debug, Serialize)]
+pub struct MemberAbi {
+    pub name: String,
+    pub r#type: TypeAbi,
+}
+
+/// A single named entry of a record, including the visibility mode it is declared under.
+#[derive(Clone, Debug, Serialize)]
+pub struct RecordEntryAbi {
+    pub name: String,
+    /// `"constant"`, `"public"`, or `"private"`.
+    pub mode: &'static str,
+    pub r#type: TypeAbi,
+}
+
+/// One callable function's interface: its ordered inputs and outputs.
+#[derive(Clone, Debug, Serialize)]
+pub struct FunctionAbi {
+    pub name: String,
+    pub inputs: Vec<ParameterAbi>,
+    pub outputs: Vec<ParameterAbi>,
+}
+
+/// A single input or output, including the visibility mode it is declared under.
+#[derive(Clone, Debug, Serialize)]
+pub struct ParameterAbi {
+    /// `"constant"`, `"public"`, `"private"`, `"record"`, or `"external_record"`.
+    pub mode: &'static str,
+    pub r#type: TypeAbi,
+}
+
+/// The full ABI of a deployed program: every callable function, in declaration order.
+#[derive(Clone, Debug, Serialize)]
+pub struct ProgramAbi {
+    pub program_id: String,
+    pub functions: Vec<FunctionAbi>,
+}
+
+/// Builds the ABI for every function declared in `program`.
+pub fn describe_program<N: Network>(program: &Program<N>) -> ProgramAbi {
+    let functions = program
+        .functions()
+        .values()
+        .map(|function| FunctionAbi {
+            name: function.name().to_string(),
+            inputs: function
+                .inputs()
+                .iter()
+                .map(|input| describe_value_type(input.value_type(), program))
+                .collect(),
+            outputs: function
+                .outputs()
+                .iter()
+                .map(|output| describe_value_type(output.value_type(), program))
+                .collect(),
+        })
+        .collect();
+
+    ProgramAbi { program_id: program.id().to_string(), functions }
+}
+
+/// Describes a single declared input or output, given the mode it carries.
+fn describe_value_type<N: Network>(value_type: &ValueType<N>, program: &Program<N>) -> ParameterAbi {
+    match value_type {
+        ValueType::Constant(plaintext_type) => {
+            ParameterAbi { mode: "constant", r#type: describe_plaintext_type(plaintext_type, program) }
+        }
+        ValueType::Public(plaintext_type) => {
+            ParameterAbi { mode: "public", r#type: describe_plaintext_type(plaintext_type, program) }
+        }
+        ValueType::Private(plaintext_type) => {
+            ParameterAbi { mode: "private", r#type: describe_plaintext_type(plaintext_type, program) }
+        }
+        ValueType::Record(identifier) => {
+            ParameterAbi { mode: "record", r#type: describe_record(identifier, program) }
+        }
+        ValueType::ExternalRecord(locator) => ParameterAbi {
+            mode: "external_record",
+            r#type: TypeAbi::ExternalRecord {
+                program_id: locator.program_id().to_string(),
+                name: locator.resource().to_string(),
+            },
+        },
+    }
+}
+
+/// Describes a plaintext type, recursively resolving struct members and vector elements.
+fn describe_plaintext_type<N: Network>(
+    plaintext_type: &snarkvm_console::program::PlaintextType<N>,
+    program: &Program<N>,
+) -> TypeAbi {
+    use snarkvm_console::program::PlaintextType;
+
+    match plaintext_type {
+        PlaintextType::Literal(literal_type) => TypeAbi::Literal { literal: literal_type.to_string() },
+        PlaintextType::Struct(identifier) => describe_struct(identifier, program),
+        PlaintextType::Vector(vector_type) => {
+            TypeAbi::Vector { element: Box::new(describe_element_type(vector_type.element_type(), program)) }
+        }
+    }
+}
+
+/// Describes a vector's element type, recursively handling nested structs and vectors.
+fn describe_element_type<N: Network>(element_type: &ElementType<N>, program: &Program<N>) -> TypeAbi {
+    match element_type {
+        ElementType::Literal(literal_type) => TypeAbi::Literal { literal: literal_type.to_string() },
+        ElementType::Struct(identifier) => describe_struct(identifier, program),
+        ElementType::Vector(vector_type) => {
+            TypeAbi::Vector { element: Box::new(describe_element_type(vector_type.element_type(), program)) }
+        }
+    }
+}
+
+/// Resolves a struct's members from the program's struct definitions.
+fn describe_struct<N: Network>(identifier: &Identifier<N>, program: &Program<N>) -> TypeAbi {
+    let members = match program.structs().get(identifier) {
+        Some(struct_) => struct_
+            .members()
+            .iter()
+            .map(|(name, plaintext_type)| MemberAbi {
+                name: name.to_string(),
+                r#type: describe_plaintext_type(plaintext_type, program),
+            })
+            .collect(),
+        // A struct referenced by name but not found is described with no resolvable members,
+        // rather than failing the whole ABI — the name alone is still useful to a client.
+        None => Vec::new(),
+    };
+
+    TypeAbi::Struct { name: identifier.to_string(), members }
+}
+
+/// Resolves a record's entries from the program's record definitions, mirroring `describe_struct`.
+fn describe_record<N: Network>(identifier: &Identifier<N>, program: &Program<N>) -> TypeAbi {
+    let entries = match program.records().get(identifier) {
+        Some(record_type) => record_type
+            .entries()
+            .iter()
+            .map(|(name, entry_type)| {
+                let (mode, plaintext_type) = match entry_type {
+                    EntryType::Constant(plaintext_type) => ("constant", plaintext_type),
+                    EntryType::Public(plaintext_type) => ("public", plaintext_type),
+                    EntryType::Private(plaintext_type) => ("private", plaintext_type),
+                };
+                RecordEntryAbi { name: name.to_string(), mode, r#type: describe_plaintext_type(plaintext_type, program) }
+            })
+            .collect(),
+        // A record referenced by name but not found is described with no resolvable entries,
+        // rather than failing the whole ABI — the name alone is still useful to a client.
+        None => Vec::new(),
+    };
+
+    TypeAbi::Record { name: identifier.to_string(), entries }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use snarkvm_console::network::Testnet3;
+    use std::str::FromStr;
+
+    type CurrentNetwork = Testnet3;
+
+    const PROGRAM_SOURCE: &str = r"
+program test.aleo;
+
+struct point:
+    x as field;
+    y as field;
+
+record token:
+    owner as address.private;
+    amount as u64.private;
+    location as point.private;
+
+function transfer:
+    input r0 as [point; 4u32].private;
+    input r1 as token.record;
+    output r0 as token.record;
+";
+
+    #[test]
+    fn describe_program_resolves_nested_vector_struct_and_record_types() {
+        let program = Program::<CurrentNetwork>::from_str(PROGRAM_SOURCE).expect("the sample program should parse");
+        let abi = describe_program(&program);
+
+        assert_eq!(abi.program_id, "test.aleo");
+        let function = abi.functions.iter().find(|f| f.name == "transfer").expect("transfer should be described");
+
+        // input r0: a vector of `point` structs.
+        match &function.inputs[0].r#type {
+            TypeAbi::Vector { element } => match element.as_ref() {
+                TypeAbi::Struct { name, members } => {
+                    assert_eq!(name, "point");
+                    assert_eq!(members.len(), 2);
+                }
+                other => panic!("expected a struct element, got {other:?}"),
+            },
+            other => panic!("expected a vector, got {other:?}"),
+        }
+
+        // input r1 / output r0: the `token` record, with its nested `point` entry resolved.
+        for parameter in [&function.inputs[1], &function.outputs[0]] {
+            match &parameter.r#type {
+                TypeAbi::Record { name, entries } => {
+                    assert_eq!(name, "token");
+                    let location = entries.iter().find(|e| e.name == "location").expect("a location entry");
+                    assert!(matches!(&location.r#type, TypeAbi::Struct { name, .. } if name == "point"));
+                }
+                other => panic!("expected a record, got {other:?}"),
+            }
+        }
+    }
+}
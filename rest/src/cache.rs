@@ -0,0 +1,110 @@
+// Copyright (C) 2019-2022 Aleo Systems Inc.
+// This file is part of the snarkVM library.
+
+// The snarkVM library is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// The snarkVM library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with the snarkVM library. If not, see <https://www.gnu.org/licenses/>.
+
+use snarkvm_compiler::{Plaintext, Record, RecordsFilter, StatePath};
+use snarkvm_console::{account::ViewKey, prelude::Network, types::Field};
+
+use indexmap::IndexMap;
+use lru::LruCache;
+use parking_lot::RwLock;
+use std::{hash::Hash, num::NonZeroUsize};
+
+/// The key a record scan is cached under. `ViewKey` doesn't implement `Hash`, so it is
+/// keyed by its canonical byte encoding instead.
+#[derive(Clone, PartialEq, Eq, Hash)]
+struct RecordCacheKey {
+    view_key_bytes: Vec<u8>,
+    filter: RecordsFilter,
+}
+
+/// A response cache sitting in front of the record-scanning and state-path REST handlers,
+/// bounded by an LRU eviction policy and invalidated wholesale whenever the ledger advances.
+pub struct QueryCache<N: Network> {
+    records: RwLock<LruCache<RecordCacheKey, IndexMap<Field<N>, Record<N, Plaintext<N>>>>>,
+    state_paths: RwLock<LruCache<Field<N>, StatePath<N>>>,
+    /// The ledger height the cache was last known to be valid for.
+    cached_height: RwLock<u32>,
+}
+
+impl<N: Network> QueryCache<N> {
+    /// Initializes an empty cache, bounding each of the record and state-path caches to `capacity` entries.
+    pub fn new(capacity: NonZeroUsize, height: u32) -> Self {
+        Self {
+            records: RwLock::new(LruCache::new(capacity)),
+            state_paths: RwLock::new(LruCache::new(capacity)),
+            cached_height: RwLock::new(height),
+        }
+    }
+
+    /// Clears every cached entry if `height` is newer than the height the cache was built for.
+    pub fn observe_height(&self, height: u32) {
+        let mut cached_height = self.cached_height.write();
+        if height > *cached_height {
+            self.records.write().clear();
+            self.state_paths.write().clear();
+            *cached_height = height;
+        }
+    }
+
+    /// Returns the cached records for `(view_key, filter)`, if present.
+    pub fn get_records(
+        &self,
+        view_key: &ViewKey<N>,
+        filter: RecordsFilter,
+    ) -> Option<IndexMap<Field<N>, Record<N, Plaintext<N>>>> {
+        let key = RecordCacheKey { view_key_bytes: view_key.to_bytes_le().ok()?, filter };
+        self.records.write().get(&key).cloned()
+    }
+
+    /// Caches `records` for `(view_key, filter)`, dropping the write if `height` — the
+    /// ledger height `records` was read at — is stale by the time the write would land.
+    ///
+    /// Holding `cached_height`'s read lock for the whole check-then-insert blocks a
+    /// concurrent `observe_height` from clearing the cache in between, which would
+    /// otherwise let a slow read started just before a block lands write its
+    /// now-stale result back in right after the clear.
+    pub fn put_records(
+        &self,
+        view_key: &ViewKey<N>,
+        filter: RecordsFilter,
+        height: u32,
+        records: IndexMap<Field<N>, Record<N, Plaintext<N>>>,
+    ) {
+        let cached_height = self.cached_height.read();
+        if height < *cached_height {
+            return;
+        }
+        if let Ok(view_key_bytes) = view_key.to_bytes_le() {
+            self.records.write().put(RecordCacheKey { view_key_bytes, filter }, records);
+        }
+    }
+
+    /// Returns the cached state path for `commitment`, if present.
+    pub fn get_state_path(&self, commitment: &Field<N>) -> Option<StatePath<N>> {
+        self.state_paths.write().get(commitment).cloned()
+    }
+
+    /// Caches `state_path` for `commitment`, dropping the write if `height` — the ledger
+    /// height `state_path` was read at — is stale by the time the write would land. See
+    /// `put_records` for why the `cached_height` read lock is held across the check-then-insert.
+    pub fn put_state_path(&self, commitment: Field<N>, height: u32, state_path: StatePath<N>) {
+        let cached_height = self.cached_height.read();
+        if height < *cached_height {
+            return;
+        }
+        self.state_paths.write().put(commitment, state_path);
+    }
+}
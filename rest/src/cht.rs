@@ -0,0 +1,208 @@
+// Copyright (C) 2019-2022 Aleo Systems Inc.
+// This file is part of the snarkVM library.
+
+// The snarkVM library is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// The snarkVM library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with the snarkVM library. If not, see <https://www.gnu.org/licenses/>.
+
+use snarkvm_compiler::{BlockStorage, Ledger, ProgramStorage};
+use snarkvm_console::{prelude::Network, types::Field};
+
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// The number of block headers committed to a single CHT section. Only a *completed*
+/// section (i.e. one whose final height has been appended to the chain) is assigned a
+/// root, so a light client can trust a root without worrying that it will change
+/// underneath it.
+pub const CHT_SECTION_SIZE: u32 = 2048;
+
+/// A single entry committed into a CHT section, keyed by its block height.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ChtEntry<N: Network> {
+    /// The block hash at this height.
+    pub block_hash: N::BlockHash,
+    /// The cumulative state root as of this height.
+    pub state_root: Field<N>,
+}
+
+/// A Merkle proof that a given `(height, entry)` pair is committed under a section's CHT root.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ChtProof<N: Network> {
+    /// The index of the section this height belongs to.
+    pub section_index: u32,
+    /// The root of the section's CHT.
+    pub section_root: Field<N>,
+    /// The entry being proven.
+    pub entry: ChtEntry<N>,
+    /// The sibling hashes along the path from the leaf to `section_root`, ordered leaf-to-root.
+    pub branch: Vec<Field<N>>,
+}
+
+/// A single completed CHT section, i.e. a Merkle trie over `CHT_SECTION_SIZE` consecutive
+/// block heights, keyed by the big-endian height within the section.
+struct ChtSection<N: Network> {
+    /// The entries in this section, indexed by height within the section.
+    entries: Vec<ChtEntry<N>>,
+    /// The layers of the binary Merkle trie, from leaves (index 0) to the root.
+    layers: Vec<Vec<Field<N>>>,
+}
+
+impl<N: Network> ChtSection<N> {
+    /// Builds a section's CHT from its (already height-ordered) entries.
+    fn build(entries: Vec<ChtEntry<N>>) -> Result<Self> {
+        if entries.len() != CHT_SECTION_SIZE as usize {
+            bail!("a CHT section must contain exactly {CHT_SECTION_SIZE} entries");
+        }
+
+        // Hash each entry into a leaf, keyed by its big-endian position in the section.
+        let mut leaves = Vec::with_capacity(entries.len());
+        for (position, entry) in entries.iter().enumerate() {
+            let preimage = [Field::<N>::from_u32(position as u32), field_from_hash(&entry.block_hash)?, entry.state_root];
+            leaves.push(N::hash_psd4(&preimage)?);
+        }
+
+        // Build the trie bottom-up, duplicating the final node in a level if it is odd-sized.
+        let mut layers = vec![leaves];
+        while layers.last().unwrap().len() > 1 {
+            let current = layers.last().unwrap();
+            let mut next = Vec::with_capacity((current.len() + 1) / 2);
+            for pair in current.chunks(2) {
+                let (left, right) = (pair[0], *pair.get(1).unwrap_or(&pair[0]));
+                next.push(N::hash_psd4(&[left, right])?);
+            }
+            layers.push(next);
+        }
+
+        Ok(Self { entries, layers })
+    }
+
+    /// Returns the section's CHT root.
+    fn root(&self) -> Field<N> {
+        self.layers.last().unwrap()[0]
+    }
+
+    /// Returns a Merkle proof for the entry at the given position within the section.
+    fn prove(&self, position: usize) -> ChtProof<N> {
+        let mut branch = Vec::with_capacity(self.layers.len() - 1);
+        let mut index = position;
+        for layer in self.layers.iter().take(self.layers.len() - 1) {
+            let sibling_index = index ^ 1;
+            branch.push(*layer.get(sibling_index).unwrap_or(&layer[index]));
+            index /= 2;
+        }
+
+        ChtProof {
+            section_index: 0,
+            section_root: self.root(),
+            entry: self.entries[position].clone(),
+            branch,
+        }
+    }
+}
+
+/// Packs a block hash into a field element for hashing, by round-tripping it through its
+/// little-endian byte encoding. Returns an error if either conversion fails.
+fn field_from_hash<N: Network>(hash: &N::BlockHash) -> Result<Field<N>> {
+    Ok(Field::<N>::from_bytes_le(&hash.to_bytes_le()?)?)
+}
+
+/// Maintains the set of completed CHT sections for a ledger, recomputing them
+/// deterministically from `BlockStorage` on construction and extending them incrementally
+/// as new blocks are appended.
+pub struct ChtRegistry<N: Network> {
+    /// The completed sections, keyed by section index.
+    sections: BTreeMap<u32, ChtSection<N>>,
+    /// The height, exclusive, up to which sections have been built.
+    synced_height: u32,
+}
+
+impl<N: Network> ChtRegistry<N> {
+    /// Initializes the registry, building every section that is already complete in storage.
+    pub fn new<B: BlockStorage<N>, P: ProgramStorage<N>>(ledger: &Ledger<N, B, P>) -> Result<Self> {
+        let mut registry = Self { sections: BTreeMap::new(), synced_height: 0 };
+        registry.sync(ledger)?;
+        Ok(registry)
+    }
+
+    /// Builds any newly-completed sections, given the ledger's current height.
+    pub fn sync<B: BlockStorage<N>, P: ProgramStorage<N>>(&mut self, ledger: &Ledger<N, B, P>) -> Result<()> {
+        let latest_height = ledger.latest_height();
+
+        // Advance one section at a time, so a sync that fails partway keeps the sections
+        // already built and the next sync resumes from the first missing one.
+        while self.synced_height + CHT_SECTION_SIZE <= latest_height.saturating_add(1) {
+            let section_index = self.synced_height / CHT_SECTION_SIZE;
+            let start = section_index * CHT_SECTION_SIZE;
+
+            let mut entries = Vec::with_capacity(CHT_SECTION_SIZE as usize);
+            for height in start..start + CHT_SECTION_SIZE {
+                let block = ledger.get_block(height)?;
+                entries.push(ChtEntry { block_hash: block.hash(), state_root: block.state_root() });
+            }
+
+            self.sections.insert(section_index, ChtSection::build(entries)?);
+            self.synced_height = start + CHT_SECTION_SIZE;
+        }
+
+        Ok(())
+    }
+
+    /// Returns a CHT proof for the given height, or `None` if its section is not yet complete.
+    pub fn prove(&self, height: u32) -> Option<ChtProof<N>> {
+        let section_index = height / CHT_SECTION_SIZE;
+        let position = (height % CHT_SECTION_SIZE) as usize;
+
+        let section = self.sections.get(&section_index)?;
+        let mut proof = section.prove(position);
+        proof.section_index = section_index;
+        Some(proof)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use snarkvm_console::network::Testnet3;
+
+    type CurrentNetwork = Testnet3;
+
+    fn sample_entries() -> Vec<ChtEntry<CurrentNetwork>> {
+        (0..CHT_SECTION_SIZE)
+            .map(|position| ChtEntry {
+                block_hash: <CurrentNetwork as Network>::BlockHash::from(Field::<CurrentNetwork>::from_u32(position)),
+                state_root: Field::<CurrentNetwork>::from_u32(position + 1),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn build_and_prove_round_trip() {
+        let entries = sample_entries();
+        let section = ChtSection::<CurrentNetwork>::build(entries).expect("a full section should build");
+
+        for position in [0usize, 1, CHT_SECTION_SIZE as usize / 2, CHT_SECTION_SIZE as usize - 1] {
+            let proof = section.prove(position);
+            assert_eq!(proof.section_root, section.root());
+            assert_eq!(proof.entry.state_root, Field::<CurrentNetwork>::from_u32(position as u32 + 1));
+            assert_eq!(proof.branch.len(), section.layers.len() - 1);
+        }
+    }
+
+    #[test]
+    fn build_rejects_a_short_section() {
+        let mut entries = sample_entries();
+        entries.pop();
+        assert!(ChtSection::<CurrentNetwork>::build(entries).is_err());
+    }
+}
@@ -0,0 +1,255 @@
+// Copyright (C) 2019-2022 Aleo Systems Inc.
+// This file is part of the snarkVM library.
+
+// The snarkVM library is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// The snarkVM library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with the snarkVM library. If not, see <https://www.gnu.org/licenses/>.
+
+use super::cache::QueryCache;
+
+use snarkvm_compiler::{Block, BlockStorage, Ledger, Plaintext, ProgramStorage, Record, RecordsFilter, Transaction};
+use snarkvm_console::{account::ViewKey, prelude::Network, types::Field};
+
+use indexmap::IndexMap;
+
+use futures_util::{SinkExt, StreamExt};
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, collections::HashSet, sync::Arc};
+use tokio::sync::broadcast;
+use warp::ws::{Message, WebSocket};
+
+/// The capacity of the broadcast channel backing the pub/sub hub. A lagging subscriber
+/// drops the oldest events rather than blocking block production or the mempool.
+const CHANNEL_CAPACITY: usize = 1024;
+
+/// An event fanned out to every subscriber, published by `start_handler` as the chain advances.
+#[derive(Clone, Debug)]
+pub enum PubSubEvent<N: Network> {
+    /// A new block was appended to the ledger.
+    Block(Block<N>),
+    /// A transaction was newly admitted to the memory pool.
+    Mempool(Transaction<N>),
+}
+
+/// The hub that `start_handler` publishes ledger events to, and that each WebSocket
+/// connection subscribes a receiver from.
+#[derive(Clone)]
+pub struct PubSubHub<N: Network> {
+    sender: broadcast::Sender<PubSubEvent<N>>,
+}
+
+impl<N: Network> PubSubHub<N> {
+    /// Initializes a new pub/sub hub.
+    pub fn new() -> Self {
+        let (sender, _) = broadcast::channel(CHANNEL_CAPACITY);
+        Self { sender }
+    }
+
+    /// Publishes an event to every current subscriber. Returns without error if there
+    /// are no subscribers connected.
+    pub fn publish(&self, event: PubSubEvent<N>) {
+        let _ = self.sender.send(event);
+    }
+
+    /// Subscribes a new receiver to the event stream.
+    fn subscribe(&self) -> broadcast::Receiver<PubSubEvent<N>> {
+        self.sender.subscribe()
+    }
+}
+
+impl<N: Network> Default for PubSubHub<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A control message sent by the client to manage its subscriptions.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ClientMessage<N: Network> {
+    /// Subscribes to a topic, receiving a server-assigned subscription ID in response.
+    Subscribe {
+        topic: Topic,
+        /// Required when `topic` is `Topic::Records`; identifies the records to match.
+        view_key: Option<ViewKey<N>>,
+    },
+    /// Cancels a previously-established subscription.
+    Unsubscribe { id: u64 },
+}
+
+/// The topics a client may subscribe to.
+#[derive(Copy, Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+enum Topic {
+    /// Every newly-appended block.
+    Blocks,
+    /// Every transaction newly admitted to the memory pool.
+    Mempool,
+    /// Newly discovered unspent records owned by a given view key.
+    Records,
+}
+
+/// A server-to-client message: either an acknowledgement of a subscription control message,
+/// or a matching event for one of the connection's active subscriptions.
+#[derive(Clone, Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ServerMessage<'a, N: Network> {
+    Subscribed { id: u64, topic: Topic },
+    Unsubscribed { id: u64 },
+    Error { message: String },
+    Block { id: u64, block: &'a Block<N> },
+    Mempool { id: u64, transaction: &'a Transaction<N> },
+    Record { id: u64, commitment: Field<N>, record: serde_json::Value },
+}
+
+/// An active subscription held by one WebSocket connection.
+enum Subscription<N: Network> {
+    Blocks,
+    Mempool,
+    /// Tracks the commitments already forwarded, so a client only ever sees a record once.
+    Records { view_key: ViewKey<N>, seen: HashSet<Field<N>> },
+}
+
+/// Handles a single `/testnet3/subscribe/ws` connection: client-issued subscribe/unsubscribe
+/// control messages come in on the socket, and matching ledger events are forwarded out.
+pub async fn handle_subscriptions<N: Network, B: 'static + BlockStorage<N>, P: 'static + ProgramStorage<N>>(
+    socket: WebSocket,
+    ledger: Arc<RwLock<Ledger<N, B, P>>>,
+    hub: PubSubHub<N>,
+    cache: Arc<QueryCache<N>>,
+) {
+    let (mut sink, mut stream) = socket.split();
+    let mut events = hub.subscribe();
+
+    let mut subscriptions: HashMap<u64, Subscription<N>> = HashMap::new();
+    let mut next_id: u64 = 0;
+
+    loop {
+        tokio::select! {
+            message = stream.next() => {
+                let Some(Ok(message)) = message else { break };
+                let Ok(text) = message.to_str() else { continue };
+
+                let reply = match serde_json::from_str::<ClientMessage<N>>(text) {
+                    Ok(ClientMessage::Subscribe { topic, view_key }) => {
+                        let id = next_id;
+                        next_id += 1;
+
+                        let subscription = match topic {
+                            Topic::Blocks => Subscription::Blocks,
+                            Topic::Mempool => Subscription::Mempool,
+                            Topic::Records => match view_key {
+                                Some(view_key) => {
+                                    // Seed `seen` with the view key's current unspent commitments,
+                                    // without forwarding them, so the client's first `Block` event
+                                    // only surfaces commitments that are newly discovered from here on.
+                                    let seen = match unspent_records(&ledger, &cache, &view_key) {
+                                        Ok(records) => records.keys().copied().collect(),
+                                        Err(()) => HashSet::new(),
+                                    };
+                                    Subscription::Records { view_key, seen }
+                                }
+                                None => {
+                                    send(&mut sink, &ServerMessage::<N>::Error {
+                                        message: "the 'records' topic requires a view_key".into(),
+                                    }).await;
+                                    continue;
+                                }
+                            },
+                        };
+
+                        subscriptions.insert(id, subscription);
+                        ServerMessage::Subscribed { id, topic }
+                    }
+                    Ok(ClientMessage::Unsubscribe { id }) => {
+                        subscriptions.remove(&id);
+                        ServerMessage::Unsubscribed { id }
+                    }
+                    Err(error) => ServerMessage::Error { message: format!("invalid control message: {error}") },
+                };
+
+                send(&mut sink, &reply).await;
+            }
+            event = events.recv() => {
+                let event = match event {
+                    Ok(event) => event,
+                    // This subscriber fell behind and the oldest events were dropped; keep going.
+                    Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                    Err(broadcast::error::RecvError::Closed) => break,
+                };
+                forward(&mut sink, &ledger, &cache, &mut subscriptions, &event).await;
+            }
+        }
+    }
+}
+
+/// Forwards `event` to every active subscription on this connection that matches it.
+async fn forward<N: Network, B: 'static + BlockStorage<N>, P: 'static + ProgramStorage<N>>(
+    sink: &mut (impl futures_util::Sink<Message> + Unpin),
+    ledger: &Arc<RwLock<Ledger<N, B, P>>>,
+    cache: &Arc<QueryCache<N>>,
+    subscriptions: &mut HashMap<u64, Subscription<N>>,
+    event: &PubSubEvent<N>,
+) {
+    for (&id, subscription) in subscriptions.iter_mut() {
+        match (subscription, event) {
+            (Subscription::Blocks, PubSubEvent::Block(block)) => {
+                send(sink, &ServerMessage::Block { id, block }).await;
+            }
+            (Subscription::Mempool, PubSubEvent::Mempool(transaction)) => {
+                send(sink, &ServerMessage::Mempool { id, transaction }).await;
+            }
+            (Subscription::Records { view_key, seen }, PubSubEvent::Block(_)) => {
+                let Ok(records) = unspent_records(ledger, cache, view_key) else { continue };
+                for (commitment, record) in records {
+                    if seen.insert(commitment) {
+                        if let Ok(record) = serde_json::to_value(&record) {
+                            send(sink, &ServerMessage::Record { id, commitment, record }).await;
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Returns the view key's current unspent records, going through the shared `QueryCache` so
+/// that a block touching none of a view key's records, or a view key shared by several
+/// subscribers, only costs one `find_records` scan rather than one per subscriber.
+fn unspent_records<N: Network, B: 'static + BlockStorage<N>, P: 'static + ProgramStorage<N>>(
+    ledger: &Arc<RwLock<Ledger<N, B, P>>>,
+    cache: &Arc<QueryCache<N>>,
+    view_key: &ViewKey<N>,
+) -> Result<IndexMap<Field<N>, Record<N, Plaintext<N>>>, ()> {
+    if let Some(records) = cache.get_records(view_key, RecordsFilter::Unspent) {
+        return Ok(records);
+    }
+
+    let (height, records) = {
+        let ledger = ledger.read();
+        let records: IndexMap<_, _> =
+            ledger.find_records(view_key, RecordsFilter::Unspent).map_err(|_| ())?.collect();
+        (ledger.latest_height(), records)
+    };
+    cache.put_records(view_key, RecordsFilter::Unspent, height, records.clone());
+    Ok(records)
+}
+
+/// Serializes and sends a single server message, silently dropping it if the client
+/// has already disconnected (the outer select loop will observe the same on its next poll).
+async fn send<N: Network>(sink: &mut (impl futures_util::Sink<Message> + Unpin), message: &ServerMessage<'_, N>) {
+    if let Ok(text) = serde_json::to_string(message) {
+        let _ = sink.send(Message::text(text)).await;
+    }
+}
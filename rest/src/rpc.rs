@@ -0,0 +1,228 @@
+// Copyright (C) 2019-2022 Aleo Systems Inc.
+// This file is part of the snarkVM library.
+
+// The snarkVM library is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// The snarkVM library is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with the snarkVM library. If not, see <https://www.gnu.org/licenses/>.
+
+use crate::{LedgerRequest, LedgerSender};
+
+use snarkvm_compiler::{BlockStorage, Ledger, ProgramStorage, Transaction};
+use snarkvm_console::prelude::Network;
+
+use futures_util::{SinkExt, StreamExt};
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{os::unix::fs::PermissionsExt, path::Path, sync::Arc};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    net::UnixListener,
+};
+use warp::ws::{Message, WebSocket};
+
+/// A JSON-RPC 2.0 request, as defined by https://www.jsonrpc.org/specification.
+#[derive(Clone, Debug, Deserialize)]
+pub struct RpcRequest {
+    /// The JSON-RPC protocol version. Validated against `"2.0"` in `RpcContext::dispatch`.
+    jsonrpc: String,
+    /// The identifier established by the client, echoed back on the response.
+    id: Option<Value>,
+    /// The name of the method to invoke, e.g. `ledger_latestHeight`.
+    method: String,
+    /// The method's parameters, if any.
+    #[serde(default)]
+    params: Value,
+}
+
+/// A JSON-RPC 2.0 error object.
+#[derive(Clone, Debug, Serialize)]
+pub struct RpcError {
+    code: i64,
+    message: String,
+}
+
+/// A JSON-RPC 2.0 response.
+#[derive(Clone, Debug, Serialize)]
+pub struct RpcResponse {
+    jsonrpc: &'static str,
+    id: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    result: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<RpcError>,
+}
+
+impl RpcResponse {
+    /// Returns a successful response carrying `result`.
+    fn ok(id: Option<Value>, result: Value) -> Self {
+        Self { jsonrpc: "2.0", id, result: Some(result), error: None }
+    }
+
+    /// Returns an error response. `-32601` is the JSON-RPC "method not found" code;
+    /// `-32602` is "invalid params".
+    fn err(id: Option<Value>, code: i64, message: impl Into<String>) -> Self {
+        Self { jsonrpc: "2.0", id, result: None, error: Some(RpcError { code, message: message.into() }) }
+    }
+}
+
+/// The shared state every JSON-RPC transport dispatches requests against.
+#[derive(Clone)]
+pub struct RpcContext<N: Network, B: BlockStorage<N>, P: ProgramStorage<N>> {
+    /// The ledger.
+    pub ledger: Arc<RwLock<Ledger<N, B, P>>>,
+    /// The ledger sender.
+    pub ledger_sender: LedgerSender<N>,
+}
+
+impl<N: Network, B: 'static + BlockStorage<N>, P: 'static + ProgramStorage<N>> RpcContext<N, B, P> {
+    /// Dispatches a single JSON-RPC request to the ledger call backing the matching REST route.
+    pub async fn dispatch(&self, request: RpcRequest) -> RpcResponse {
+        let id = request.id.clone();
+
+        // `-32600` is the JSON-RPC "Invalid Request" code.
+        if request.jsonrpc != "2.0" {
+            return RpcResponse::err(id, -32600, format!("unsupported jsonrpc version: {}", request.jsonrpc));
+        }
+
+        match self.call(&request.method, request.params).await {
+            Ok(result) => RpcResponse::ok(id, result),
+            Err(RpcDispatchError::MethodNotFound) => {
+                RpcResponse::err(id, -32601, format!("method not found: {}", request.method))
+            }
+            Err(RpcDispatchError::InvalidParams(message)) => RpcResponse::err(id, -32602, message),
+            Err(RpcDispatchError::Internal(message)) => RpcResponse::err(id, -32000, message),
+        }
+    }
+
+    /// Routes a method name to the same ledger calls (or ledger-sender request) that the
+    /// REST route handlers make; the REST handler functions themselves are not invoked.
+    async fn call(&self, method: &str, params: Value) -> Result<Value, RpcDispatchError> {
+        match method {
+            "ledger_latestHeight" => Ok(serde_json::to_value(self.ledger.read().latest_height())?),
+            "ledger_latestHash" => Ok(serde_json::to_value(self.ledger.read().latest_hash())?),
+            "ledger_latestBlock" => {
+                let block = self.ledger.read().latest_block().map_err(RpcDispatchError::internal)?;
+                Ok(serde_json::to_value(block)?)
+            }
+            "ledger_getBlock" => {
+                let height: u32 = serde_json::from_value(params)
+                    .map_err(|error| RpcDispatchError::InvalidParams(format!("expected a block height: {error}")))?;
+                let block = self.ledger.read().get_block(height).map_err(RpcDispatchError::internal)?;
+                Ok(serde_json::to_value(block)?)
+            }
+            "ledger_broadcastTransaction" => {
+                let transaction: Transaction<N> = serde_json::from_value(params)
+                    .map_err(|error| RpcDispatchError::InvalidParams(format!("expected a transaction: {error}")))?;
+                self.ledger_sender
+                    .send(LedgerRequest::TransactionBroadcast(transaction))
+                    .await
+                    .map_err(RpcDispatchError::internal)?;
+                Ok(Value::String("OK".into()))
+            }
+            _ => Err(RpcDispatchError::MethodNotFound),
+        }
+    }
+}
+
+/// An error encountered while dispatching a JSON-RPC request, mapped to a JSON-RPC error code.
+enum RpcDispatchError {
+    MethodNotFound,
+    InvalidParams(String),
+    Internal(String),
+}
+
+impl RpcDispatchError {
+    fn internal(error: impl std::fmt::Display) -> Self {
+        Self::Internal(format!("{error}"))
+    }
+}
+
+impl From<serde_json::Error> for RpcDispatchError {
+    fn from(error: serde_json::Error) -> Self {
+        Self::Internal(format!("{error}"))
+    }
+}
+
+/// Handles a single JSON-RPC WebSocket connection, dispatching each incoming text frame
+/// and replying on the same connection.
+pub async fn handle_websocket<N: Network, B: 'static + BlockStorage<N>, P: 'static + ProgramStorage<N>>(
+    socket: WebSocket,
+    context: RpcContext<N, B, P>,
+) {
+    let (mut sender, mut receiver) = socket.split();
+
+    while let Some(Ok(message)) = receiver.next().await {
+        let Ok(text) = message.to_str() else {
+            continue;
+        };
+
+        let response = match serde_json::from_str::<RpcRequest>(text) {
+            Ok(request) => context.dispatch(request).await,
+            Err(error) => RpcResponse::err(None, -32700, format!("parse error: {error}")),
+        };
+
+        let Ok(response) = serde_json::to_string(&response) else {
+            continue;
+        };
+        if sender.send(Message::text(response)).await.is_err() {
+            break;
+        }
+    }
+}
+
+/// Starts the Unix-domain-socket IPC transport, for same-host tooling that would rather
+/// avoid the network stack entirely. Requests and responses are newline-delimited JSON-RPC;
+/// each accepted connection is served independently. A line that is not a valid request gets
+/// a parse-error response and the connection stays open; a read error ends only that
+/// connection, not the listener.
+pub async fn serve_ipc<N: Network, B: 'static + BlockStorage<N>, P: 'static + ProgramStorage<N>>(
+    socket_path: impl AsRef<Path>,
+    context: RpcContext<N, B, P>,
+) -> std::io::Result<()> {
+    // Remove a stale socket file left behind by a previous, uncleanly-terminated run.
+    let _ = std::fs::remove_file(&socket_path);
+
+    let listener = UnixListener::bind(socket_path.as_ref())?;
+
+    // Restrict the socket to its owner: any other local user on a shared host would
+    // otherwise be able to connect and call e.g. `ledger_broadcastTransaction`.
+    std::fs::set_permissions(socket_path.as_ref(), std::fs::Permissions::from_mode(0o600))?;
+
+    loop {
+        let (stream, _) = listener.accept().await?;
+        let context = context.clone();
+        tokio::spawn(async move {
+            let (reader, mut writer) = stream.into_split();
+            let mut lines = BufReader::new(reader).lines();
+
+            while let Ok(Some(line)) = lines.next_line().await {
+                if line.trim().is_empty() {
+                    continue;
+                }
+
+                let response = match serde_json::from_str::<RpcRequest>(&line) {
+                    Ok(request) => context.dispatch(request).await,
+                    Err(error) => RpcResponse::err(None, -32700, format!("parse error: {error}")),
+                };
+
+                let Ok(mut response) = serde_json::to_string(&response) else {
+                    continue;
+                };
+                response.push('\n');
+                if writer.write_all(response.as_bytes()).await.is_err() {
+                    break;
+                }
+            }
+        });
+    }
+}
@@ -14,25 +14,48 @@
 // You should have received a copy of the GNU General Public License
 // along with the snarkVM library. If not, see <https://www.gnu.org/licenses/>.
 
+mod abi;
+mod cache;
+mod cht;
+mod pubsub;
+mod rpc;
+
 use crate::{with, LedgerReceiver, LedgerRequest, LedgerSender, OrReject, ServerError};
+use cache::QueryCache;
+use cht::ChtRegistry;
+use pubsub::{PubSubEvent, PubSubHub};
+use rpc::RpcContext;
 
 use snarkvm_compiler::{BlockStorage, Ledger, ProgramStorage, RecordsFilter, Transaction};
-use snarkvm_console::{account::ViewKey, prelude::Network, types::Field};
+use snarkvm_console::{account::ViewKey, prelude::Network, program::ProgramID, types::Field};
 
 use anyhow::Result;
 use core::marker::PhantomData;
 use indexmap::IndexMap;
 use parking_lot::RwLock;
-use std::sync::Arc;
+use std::{net::SocketAddr, num::NonZeroUsize, path::PathBuf, sync::Arc, time::Duration};
 use tokio::{sync::mpsc, task::JoinHandle};
 use warp::{http::StatusCode, reject, reply, Filter, Rejection, Reply};
 
+/// The default number of entries kept in the record-scan and state-path response caches.
+const DEFAULT_CACHE_CAPACITY: usize = 1_000;
+
+/// How often `start_handler` polls `Ledger::latest_height` to notice blocks appended by
+/// consensus/sync code, rather than via a message on `ledger_receiver`.
+const BLOCK_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
 /// A server for the ledger.
 pub struct Server<N: Network, B: BlockStorage<N>, P: ProgramStorage<N>> {
     /// The ledger.
     ledger: Arc<RwLock<Ledger<N, B, P>>>,
     /// The ledger sender.
     ledger_sender: LedgerSender<N>,
+    /// The canonical-hash-trie registry, used to serve light-client proofs.
+    cht: Arc<RwLock<ChtRegistry<N>>>,
+    /// The pub/sub hub, used to fan out new blocks and records over WebSocket.
+    pubsub: PubSubHub<N>,
+    /// The response cache for record scans and state-path lookups.
+    cache: Arc<QueryCache<N>>,
     /// The server handles.
     handles: Vec<JoinHandle<()>>,
     /// PhantomData.
@@ -40,10 +63,17 @@ pub struct Server<N: Network, B: BlockStorage<N>, P: ProgramStorage<N>> {
 }
 
 impl<N: Network, B: 'static + BlockStorage<N>, P: 'static + ProgramStorage<N>> Server<N, B, P> {
-    /// Initializes a new instance of the server.
+    /// Initializes a new instance of the server, binding the HTTP/WebSocket listener to
+    /// `addr` and, if `ipc_path` is set, additionally serving JSON-RPC over a Unix-domain
+    /// socket at that path for same-host tooling. `cache_capacity` bounds the number of
+    /// entries kept in the record-scan and state-path response caches, defaulting to
+    /// [`DEFAULT_CACHE_CAPACITY`] when `None`.
     pub fn start(
         ledger: Arc<RwLock<Ledger<N, B, P>>>,
         additional_routes: Option<impl Filter<Extract = impl Reply, Error = Rejection> + Clone + Sync + Send + 'static>,
+        addr: SocketAddr,
+        ipc_path: Option<PathBuf>,
+        cache_capacity: Option<NonZeroUsize>,
     ) -> Result<(Self, LedgerReceiver<N>)> {
         // Initialize a channel to send requests to the ledger.
         let (ledger_sender, ledger_receiver) = mpsc::channel(64);
@@ -51,13 +81,32 @@ impl<N: Network, B: 'static + BlockStorage<N>, P: 'static + ProgramStorage<N>> S
         // Initialize a vector for the server handles.
         let mut handles = Vec::new();
 
+        // Initialize the CHT registry, recomputing every completed section from storage.
+        let cht = Arc::new(RwLock::new(ChtRegistry::new(&*ledger.read())?));
+
+        // Initialize the pub/sub hub for live block and record notifications.
+        let pubsub = PubSubHub::new();
+
+        // Initialize the record-scan and state-path response cache.
+        let cache_capacity = cache_capacity
+            .unwrap_or_else(|| NonZeroUsize::new(DEFAULT_CACHE_CAPACITY).expect("default cache capacity is nonzero"));
+        let cache = Arc::new(QueryCache::new(cache_capacity, ledger.read().latest_height()));
+
+        // Initialize the JSON-RPC context, shared across the HTTP, WebSocket, and IPC transports.
+        let rpc_context = RpcContext { ledger: ledger.clone(), ledger_sender: ledger_sender.clone() };
+
         // Initialize the routes.
-        let routes = Self::routes(ledger.clone(), ledger_sender.clone());
+        let routes = Self::routes(
+            ledger.clone(),
+            ledger_sender.clone(),
+            cht.clone(),
+            pubsub.clone(),
+            rpc_context.clone(),
+            cache.clone(),
+        );
 
         // Spawn the server.
         handles.push(tokio::spawn(async move {
-            let addr = ([0, 0, 0, 0], 80);
-
             // Start the server with optional additional routes.
             match additional_routes {
                 Some(additional_routes) => {
@@ -69,15 +118,30 @@ impl<N: Network, B: 'static + BlockStorage<N>, P: 'static + ProgramStorage<N>> S
             }
         }));
 
-        let server = Self { ledger, ledger_sender, handles, _phantom: PhantomData };
+        // Spawn the Unix-domain-socket IPC transport, if requested.
+        if let Some(ipc_path) = ipc_path {
+            let rpc_context = rpc_context.clone();
+            handles.push(tokio::spawn(async move {
+                if let Err(error) = rpc::serve_ipc(ipc_path, rpc_context).await {
+                    warn!("⚠️ JSON-RPC IPC transport stopped: {error}");
+                }
+            }));
+        }
+
+        let server = Self { ledger, ledger_sender, cht, pubsub, cache, handles, _phantom: PhantomData };
 
         Ok((server, ledger_receiver))
     }
 
-    /// Initializes the routes, given the ledger and ledger sender.
+    /// Initializes the routes, given the ledger, ledger sender, CHT registry, pub/sub hub,
+    /// JSON-RPC context, and response cache.
     fn routes(
         ledger: Arc<RwLock<Ledger<N, B, P>>>,
         ledger_sender: LedgerSender<N>,
+        cht: Arc<RwLock<ChtRegistry<N>>>,
+        pubsub: PubSubHub<N>,
+        rpc_context: RpcContext<N, B, P>,
+        cache: Arc<QueryCache<N>>,
     ) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
         // GET /testnet3/latest/height
         let latest_height = warp::get()
@@ -109,14 +173,22 @@ impl<N: Network, B: 'static + BlockStorage<N>, P: 'static + ProgramStorage<N>> S
             .and(warp::body::content_length_limit(128))
             .and(warp::body::json())
             .and(with(ledger.clone()))
+            .and(with(cache.clone()))
             .and_then(Self::state_path);
 
+        // GET /testnet3/cht/{height}
+        let cht_proof = warp::get()
+            .and(warp::path!("testnet3" / "cht" / u32))
+            .and(with(cht))
+            .and_then(Self::cht_proof);
+
         // GET /testnet3/records/all
         let records_all = warp::get()
             .and(warp::path!("testnet3" / "records" / "all"))
             .and(warp::body::content_length_limit(128))
             .and(warp::body::json())
             .and(with(ledger.clone()))
+            .and(with(cache.clone()))
             .and_then(Self::records_all);
 
         // GET /testnet3/records/spent
@@ -125,6 +197,7 @@ impl<N: Network, B: 'static + BlockStorage<N>, P: 'static + ProgramStorage<N>> S
             .and(warp::body::content_length_limit(128))
             .and(warp::body::json())
             .and(with(ledger.clone()))
+            .and(with(cache.clone()))
             .and_then(Self::records_spent);
 
         // GET /testnet3/records/unspent
@@ -133,6 +206,7 @@ impl<N: Network, B: 'static + BlockStorage<N>, P: 'static + ProgramStorage<N>> S
             .and(warp::body::content_length_limit(128))
             .and(warp::body::json())
             .and(with(ledger.clone()))
+            .and(with(cache.clone()))
             .and_then(Self::records_unspent);
 
         // GET /testnet3/transactions/{height}
@@ -146,9 +220,18 @@ impl<N: Network, B: 'static + BlockStorage<N>, P: 'static + ProgramStorage<N>> S
             .and(warp::path!("testnet3" / "transaction" / ..))
             .and(warp::path::param::<N::TransactionID>())
             .and(warp::path::end())
-            .and(with(ledger))
+            .and(with(ledger.clone()))
             .and_then(Self::get_transaction);
 
+        // GET /testnet3/program/{id}/abi
+        let program_abi = warp::get()
+            .and(warp::path!("testnet3" / "program" / ..))
+            .and(warp::path::param::<ProgramID<N>>())
+            .and(warp::path("abi"))
+            .and(warp::path::end())
+            .and(with(ledger.clone()))
+            .and_then(Self::program_abi);
+
         // POST /testnet3/transaction/broadcast
         let transaction_broadcast = warp::post()
             .and(warp::path!("testnet3" / "transaction" / "broadcast"))
@@ -157,18 +240,50 @@ impl<N: Network, B: 'static + BlockStorage<N>, P: 'static + ProgramStorage<N>> S
             .and(with(ledger_sender))
             .and_then(Self::transaction_broadcast);
 
+        // POST /testnet3/rpc
+        let rpc = warp::post()
+            .and(warp::path!("testnet3" / "rpc"))
+            .and(warp::body::content_length_limit(10 * 1024 * 1024))
+            .and(warp::body::json())
+            .and(with(rpc_context.clone()))
+            .and_then(Self::rpc);
+
+        // GET /testnet3/rpc/ws (WebSocket upgrade)
+        let rpc_ws = warp::path!("testnet3" / "rpc" / "ws").and(warp::ws()).and(with(rpc_context)).map(
+            |ws: warp::ws::Ws, rpc_context: RpcContext<N, B, P>| {
+                ws.on_upgrade(move |socket| rpc::handle_websocket(socket, rpc_context))
+            },
+        );
+
+        // GET /testnet3/subscribe/ws (WebSocket upgrade)
+        let subscribe_ws = warp::path!("testnet3" / "subscribe" / "ws")
+            .and(warp::ws())
+            .and(with(ledger))
+            .and(with(pubsub))
+            .and(with(cache))
+            .map(
+                |ws: warp::ws::Ws, ledger: Arc<RwLock<Ledger<N, B, P>>>, pubsub: PubSubHub<N>, cache: Arc<QueryCache<N>>| {
+                    ws.on_upgrade(move |socket| pubsub::handle_subscriptions(socket, ledger, pubsub, cache))
+                },
+            );
+
         // Return the list of routes.
         latest_height
             .or(latest_hash)
             .or(latest_block)
             .or(get_block)
+            .or(cht_proof)
             .or(state_path)
             .or(records_all)
             .or(records_spent)
             .or(records_unspent)
             .or(get_transactions)
             .or(get_transaction)
+            .or(program_abi)
             .or(transaction_broadcast)
+            .or(rpc)
+            .or(rpc_ws)
+            .or(subscribe_ws)
     }
 
     /// Initializes a ledger handler.
@@ -177,19 +292,58 @@ impl<N: Network, B: 'static + BlockStorage<N>, P: 'static + ProgramStorage<N>> S
         ledger: Arc<RwLock<Ledger<N, B, P>>>,
         mut ledger_receiver: LedgerReceiver<N>,
     ) -> JoinHandle<()> {
+        let cht = self.cht.clone();
+        let pubsub = self.pubsub.clone();
+        let cache = self.cache.clone();
         tokio::spawn(async move {
-            while let Some(request) = ledger_receiver.recv().await {
-                match request {
-                    LedgerRequest::TransactionBroadcast(transaction) => {
-                        let transaction_id = transaction.id();
-                        match ledger.write().add_to_memory_pool(transaction) {
-                            Ok(()) => trace!("✉️ Added transaction '{transaction_id}' to the memory pool"),
-                            Err(error) => {
-                                warn!("⚠️ Failed to add transaction '{transaction_id}' to the memory pool: {error}")
+            let mut published_height = ledger.read().latest_height();
+            // Blocks are appended to the ledger by consensus/sync code holding the same
+            // `Arc<RwLock<Ledger>>`, not by anything sent over `ledger_receiver` — so block
+            // advancement is observed by polling, not as a side effect of handling a request.
+            let mut poll_interval = tokio::time::interval(BLOCK_POLL_INTERVAL);
+
+            loop {
+                tokio::select! {
+                    request = ledger_receiver.recv() => {
+                        match request {
+                            Some(LedgerRequest::TransactionBroadcast(transaction)) => {
+                                let transaction_id = transaction.id();
+                                match ledger.write().add_to_memory_pool(transaction.clone()) {
+                                    Ok(()) => {
+                                        trace!("✉️ Added transaction '{transaction_id}' to the memory pool");
+                                        pubsub.publish(PubSubEvent::Mempool(transaction));
+                                    }
+                                    Err(error) => {
+                                        warn!("⚠️ Failed to add transaction '{transaction_id}' to the memory pool: {error}")
+                                    }
+                                }
                             }
+                            // The sender side was dropped; stop the handler.
+                            None => break,
                         }
                     }
-                };
+                    _ = poll_interval.tick() => {}
+                }
+
+                // Build any CHT sections that have become complete since the ledger last advanced.
+                if let Err(error) = cht.write().sync(&*ledger.read()) {
+                    warn!("⚠️ Failed to sync the CHT registry: {error}");
+                }
+
+                let latest_height = ledger.read().latest_height();
+
+                // Invalidate the response cache now that the ledger has advanced, before any block
+                // event goes out, so a subscriber never reads a pre-block record set from the cache.
+                cache.observe_height(latest_height);
+
+                // Publish a block event for every height appended since the ledger last advanced.
+                for height in (published_height + 1)..=latest_height {
+                    match ledger.read().get_block(height) {
+                        Ok(block) => pubsub.publish(PubSubEvent::Block(block)),
+                        Err(error) => warn!("⚠️ Failed to fetch block {height} for pub/sub: {error}"),
+                    }
+                }
+                published_height = latest_height;
             }
         })
     }
@@ -216,43 +370,80 @@ impl<N: Network, B: 'static + BlockStorage<N>, P: 'static + ProgramStorage<N>> S
         Ok(reply::json(&ledger.read().get_block(height).or_reject()?))
     }
 
+    /// Returns a light-client CHT proof for the given block height, if its section is complete.
+    async fn cht_proof(height: u32, cht: Arc<RwLock<ChtRegistry<N>>>) -> Result<impl Reply, Rejection> {
+        Ok(reply::json(&cht.read().prove(height).or_reject()?))
+    }
+
     /// Returns the state path for the given commitment.
-    async fn state_path(commitment: Field<N>, ledger: Arc<RwLock<Ledger<N, B, P>>>) -> Result<impl Reply, Rejection> {
-        Ok(reply::json(&ledger.read().to_state_path(&commitment).or_reject()?))
+    async fn state_path(
+        commitment: Field<N>,
+        ledger: Arc<RwLock<Ledger<N, B, P>>>,
+        cache: Arc<QueryCache<N>>,
+    ) -> Result<impl Reply, Rejection> {
+        // Return the cached state path, if present.
+        if let Some(state_path) = cache.get_state_path(&commitment) {
+            return Ok(reply::json(&state_path));
+        }
+
+        // Otherwise compute, cache, and return it. The height is read from the same locked
+        // snapshot as the state path, so it accurately tags which block `state_path` reflects.
+        let (height, state_path) = {
+            let ledger = ledger.read();
+            (ledger.latest_height(), ledger.to_state_path(&commitment).or_reject()?)
+        };
+        cache.put_state_path(commitment, height, state_path.clone());
+        Ok(reply::json(&state_path))
     }
 
     /// Returns all of the records for the given view key.
-    async fn records_all(view_key: ViewKey<N>, ledger: Arc<RwLock<Ledger<N, B, P>>>) -> Result<impl Reply, Rejection> {
-        // Fetch the records using the view key.
-        let records: IndexMap<_, _> = ledger.read().find_records(&view_key, RecordsFilter::All).or_reject()?.collect();
-        println!("Records:\n{:#?}", records);
-        // Return the records.
-        Ok(reply::with_status(reply::json(&records), StatusCode::OK))
+    async fn records_all(
+        view_key: ViewKey<N>,
+        ledger: Arc<RwLock<Ledger<N, B, P>>>,
+        cache: Arc<QueryCache<N>>,
+    ) -> Result<impl Reply, Rejection> {
+        Self::records(view_key, RecordsFilter::All, ledger, cache).await
     }
 
     /// Returns the spent records for the given view key.
     async fn records_spent(
         view_key: ViewKey<N>,
         ledger: Arc<RwLock<Ledger<N, B, P>>>,
+        cache: Arc<QueryCache<N>>,
     ) -> Result<impl Reply, Rejection> {
-        // Fetch the records using the view key.
-        let records =
-            ledger.read().find_records(&view_key, RecordsFilter::Spent).or_reject()?.collect::<IndexMap<_, _>>();
-        println!("Records:\n{:#?}", records);
-        // Return the records.
-        Ok(reply::with_status(reply::json(&records), StatusCode::OK))
+        Self::records(view_key, RecordsFilter::Spent, ledger, cache).await
     }
 
     /// Returns the unspent records for the given view key.
     async fn records_unspent(
         view_key: ViewKey<N>,
         ledger: Arc<RwLock<Ledger<N, B, P>>>,
+        cache: Arc<QueryCache<N>>,
     ) -> Result<impl Reply, Rejection> {
-        // Fetch the records using the view key.
-        let records =
-            ledger.read().find_records(&view_key, RecordsFilter::Unspent).or_reject()?.collect::<IndexMap<_, _>>();
-        println!("Records:\n{:#?}", records);
-        // Return the records.
+        Self::records(view_key, RecordsFilter::Unspent, ledger, cache).await
+    }
+
+    /// Returns the records matching `filter` for the given view key, using the cache
+    /// to avoid re-scanning the ledger for a view key and filter seen since the last block.
+    async fn records(
+        view_key: ViewKey<N>,
+        filter: RecordsFilter,
+        ledger: Arc<RwLock<Ledger<N, B, P>>>,
+        cache: Arc<QueryCache<N>>,
+    ) -> Result<impl Reply, Rejection> {
+        // Return the cached records, if present.
+        if let Some(records) = cache.get_records(&view_key, filter.clone()) {
+            return Ok(reply::with_status(reply::json(&records), StatusCode::OK));
+        }
+
+        // Otherwise scan the ledger for them. The height is read from the same locked
+        // snapshot as the scan, so it accurately tags which block `records` reflects.
+        let (height, records) = {
+            let ledger = ledger.read();
+            let records: IndexMap<_, _> = ledger.find_records(&view_key, filter.clone()).or_reject()?.collect();
+            (ledger.latest_height(), records)
+        };
+        cache.put_records(&view_key, filter, height, records.clone());
         Ok(reply::with_status(reply::json(&records), StatusCode::OK))
     }
 
@@ -269,6 +460,20 @@ impl<N: Network, B: 'static + BlockStorage<N>, P: 'static + ProgramStorage<N>> S
         Ok(reply::json(&ledger.read().get_transaction(transaction_id).or_reject()?))
     }
 
+    /// Returns the ABI of the deployed program with the given ID.
+    async fn program_abi(
+        program_id: ProgramID<N>,
+        ledger: Arc<RwLock<Ledger<N, B, P>>>,
+    ) -> Result<impl Reply, Rejection> {
+        let program = ledger.read().get_program(program_id).or_reject()?;
+        Ok(reply::json(&abi::describe_program(&program)))
+    }
+
+    /// Dispatches a JSON-RPC 2.0 request received over HTTP to the matching handler.
+    async fn rpc(request: rpc::RpcRequest, rpc_context: RpcContext<N, B, P>) -> Result<impl Reply, Rejection> {
+        Ok(reply::json(&rpc_context.dispatch(request).await))
+    }
+
     /// Broadcasts the transaction to the ledger.
     async fn transaction_broadcast(
         transaction: Transaction<N>,